@@ -1,6 +1,10 @@
 use std::io;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+use rand::Rng;
 use rubot::{Bot, Game};
 
 macro_rules! log {
@@ -12,12 +16,141 @@ macro_rules! log {
     };
 }
 
-use shakmaty::{fen::Fen, uci::Uci, Color, Move, MoveList, Outcome, Position, Role, Setup};
+use shakmaty::{
+    fen::Fen, uci::Uci, Bitboard, Board, Color, Move, MoveList, Outcome, Position, Role, Setup,
+    Square,
+};
+
+/// How strongly a position's second occurrence pulls the fitness towards a
+/// draw: `2` halves the leading side's advantage (a third occurrence would
+/// be an actual draw, scored `0` like the `Outcome::Draw` branch below).
+const REPETITION_DRAW_PULL: i32 = 2;
+
+/// Weight applied to the mobility term: the difference between the mover's
+/// own legal move count and the opponent's reply count.
+const MOBILITY_WEIGHT: i32 = 2;
+
+/// Keeps the combined material/positional/mobility score far away from the
+/// `i32::MAX`/`MIN` terminal sentinels used for checkmate.
+const FITNESS_BOUND: i32 = 100_000;
+
+// Piece-square tables, indexed `rank * 8 + file` (a1 = 0, h8 = 63), from
+// White's point of view; values based on the well known "simplified
+// evaluation function" tables. Black pieces read the rank-flipped index
+// (`index ^ 56` swaps rank 1 with rank 8, rank 2 with rank 7, ...) since the
+// file bits (0-2) are left untouched and the rank bits (3-5) are exactly
+// what `56 = 0b111000` flips.
+#[rustfmt::skip]
+const PAWN_PST: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,   5,  10,  25,  25,  10,   5,   5,
+    10,  10,  20,  30,  30,  20,  10,  10,
+    50,  50,  50,  50,  50,  50,  50,  50,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const KNIGHT_PST: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+#[rustfmt::skip]
+const BISHOP_PST: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+#[rustfmt::skip]
+const ROOK_PST: [i32; 64] = [
+     0,   0,   0,   5,   5,   0,   0,   0,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     5,  10,  10,  10,  10,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const QUEEN_PST: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+#[rustfmt::skip]
+const KING_PST: [i32; 64] = [
+     20,  30,  10,   0,   0,  10,  30,  20,
+     20,  20,   0,   0,   0,   0,  20,  20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+];
+
+/// Looks up `square`'s positional bonus for a piece of the given `role` and
+/// `color`.
+fn piece_square_value(role: Role, color: Color, square: shakmaty::Square) -> i32 {
+    let table = match role {
+        Role::Pawn => &PAWN_PST,
+        Role::Knight => &KNIGHT_PST,
+        Role::Bishop => &BISHOP_PST,
+        Role::Rook => &ROOK_PST,
+        Role::Queen => &QUEEN_PST,
+        Role::King => &KING_PST,
+    };
+
+    let index = square.rank() as usize * 8 + square.file() as usize;
+    table[if color == Color::White {
+        index
+    } else {
+        index ^ 56
+    }]
+}
+
+/// A repetition key covering everything that makes two positions actually
+/// equal under the threefold rule: piece placement, side to move, castling
+/// rights and the en-passant square. Board + side to move alone would call
+/// two positions equal even when one has forfeited castling rights the
+/// other still holds.
+type RepetitionKey = (Board, Color, Bitboard, Option<Square>);
+
+fn repetition_key(position: &shakmaty::Chess) -> RepetitionKey {
+    (
+        position.board().clone(),
+        position.turn(),
+        position.castling_rights(),
+        position.ep_square(),
+    )
+}
 
 /// this example requires a newtype due to orphan rules, as both shakmaty::Chess and rubot::Game
 /// are from a different crate
+///
+/// the second field is a short history of previously reached repetition
+/// keys, used to steer `execute` away from repeating a winning position
+/// (see the repetition handling below)
 #[derive(Debug, Clone, Default)]
-struct Chess(shakmaty::Chess);
+struct Chess(shakmaty::Chess, Vec<RepetitionKey>);
 
 impl Game for Chess {
     type Player = Color;
@@ -45,7 +178,7 @@ impl Game for Chess {
             }
         } else {
             let mut fitness = 0;
-            for (_square, piece) in self.0.board().pieces() {
+            for (square, piece) in self.0.board().pieces() {
                 // values based on https://medium.freecodecamp.org/simple-chess-ai-step-by-step-1d55a9266977
                 let value = match piece.role {
                     Role::Pawn => 10,
@@ -54,7 +187,7 @@ impl Game for Chess {
                     Role::Rook => 50,
                     Role::Queen => 90,
                     Role::King => 900,
-                };
+                } + piece_square_value(piece.role, piece.color, square);
 
                 if piece.color == *player {
                     fitness += value;
@@ -62,20 +195,291 @@ impl Game for Chess {
                     fitness -= value;
                 }
             }
-            fitness
+
+            // the position just reached is the opponent's to move, so its
+            // legal move count is the reply count to the move just played;
+            // `swap_turn` is a null-ish probe for the mover's own mobility
+            // in that same resulting position (it can fail if the probe
+            // would leave the opponent's king in check, in which case we
+            // just don't score a mobility term).
+            let opponent_mobility = self.0.legals().len() as i32;
+            let own_mobility = self
+                .0
+                .clone()
+                .swap_turn()
+                .map(|pos| pos.legals().len() as i32)
+                .unwrap_or(opponent_mobility);
+            fitness += MOBILITY_WEIGHT * (own_mobility - opponent_mobility);
+            fitness = fitness.clamp(-FITNESS_BOUND, FITNESS_BOUND);
+
+            let position = repetition_key(&self.0);
+            let occurrences = self.1.iter().filter(|p| **p == position).count();
+            self.1.push(position);
+
+            if occurrences >= 2 {
+                // third occurrence: shakmaty would let either side claim a
+                // draw here, so agree with the `Outcome::Draw` branch above.
+                0
+            } else if occurrences == 1 {
+                // second occurrence: pull the score halfway towards a draw so
+                // the leading side avoids repeating and the trailing side
+                // seeks it out.
+                fitness - fitness / REPETITION_DRAW_PULL
+            } else {
+                fitness
+            }
         }
     }
 }
 
-use vampirc_uci::{UciMessage, UciTimeControl};
+use vampirc_uci::{UciMessage, UciOptionConfig, UciTimeControl};
 
 fn respond(msg: UciMessage) {
     println!("{}", msg);
 }
 
+/// Engine-wide settings controlled through `setoption`, on top of the
+/// per-search parameters that come in with every `go`.
+#[derive(Debug, Clone)]
+struct EngineOptions {
+    /// Fixed search depth to use when a `go` doesn't specify one itself.
+    depth: Option<u8>,
+    /// Safety margin subtracted from the computed time budget to account for
+    /// GUI/transport latency.
+    move_overhead: Duration,
+    /// Whether to play below `bot.select`'s full strength, per `UCI_LimitStrength`.
+    limit_strength: bool,
+    /// Target playing strength used while `limit_strength` is set, per `UCI_Elo`.
+    elo: i32,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            depth: None,
+            move_overhead: Duration::from_millis(30),
+            limit_strength: false,
+            elo: MAX_ELO,
+        }
+    }
+}
+
+/// Top of the `UCI_Elo` range, i.e. unrestricted strength.
+const MAX_ELO: i32 = 2850;
+/// Bottom of the `UCI_Elo` range.
+const MIN_ELO: i32 = 500;
+
+/// `rubot::Bot` is only ever driven by a `Duration`, it has no notion of a
+/// search depth or node count. We approximate a depth/node request by
+/// handing it a generous duration cap instead of rejecting it outright.
+fn duration_for_depth(depth: u8) -> Duration {
+    Duration::from_millis(500 * u64::from(depth.min(60)))
+}
+
+/// Smallest move-time budget ever handed to `bot.select`, so a near-flagging
+/// clock still produces a `Duration` search can run with.
+const MIN_MOVE_TIME_MS: u64 = 50;
+
+/// Allocates a move-time budget (in ms) from the remaining clock `time_left`
+/// and `increment`, honoring `moves_to_go` when the GUI announces it and
+/// otherwise assuming about 30 moves remain. `move_overhead` is reserved as
+/// a safety margin for GUI/transport latency and is never dipped into,
+/// except to keep the budget above `MIN_MOVE_TIME_MS`.
+fn allocate_move_time(
+    time_left: u64,
+    increment: u64,
+    moves_to_go: Option<u8>,
+    move_overhead: Duration,
+) -> u64 {
+    let available = time_left.saturating_sub(move_overhead.as_millis() as u64);
+
+    let budget = match moves_to_go {
+        // plus a couple of reserve moves so the allocator doesn't drain the
+        // clock right as `moves_to_go` runs out.
+        Some(moves_to_go) => available / (u64::from(moves_to_go) + 2),
+        None => available / 30,
+    };
+
+    // the `MIN_MOVE_TIME_MS` floor is a usability minimum, not a license to
+    // spend more than the clock actually has left.
+    (budget + increment * 3 / 4)
+        .min(available)
+        .max(MIN_MOVE_TIME_MS)
+        .min(time_left)
+}
+
+/// A `go ponder`/`go infinite` search running on its own thread so the main
+/// loop stays free to read further UCI input (`stop`, `ponderhit`, ...).
+struct BackgroundSearch {
+    handle: JoinHandle<()>,
+    stop: Arc<AtomicBool>,
+    best_move: Arc<Mutex<Option<Move>>>,
+}
+
+/// Cap on the per-call search budget `BackgroundSearch` grows towards; keeps
+/// `stop`/`ponderhit` latency bounded even if the GUI lets us ponder for a
+/// very long time.
+const PONDER_MAX_BURST_MS: u64 = 5_000;
+
+impl BackgroundSearch {
+    /// Repeatedly searches `game`, refining `best_move` after every call,
+    /// until `stop` is set from the main thread. `rubot::Bot` doesn't expose
+    /// whether it retains any search state across `select` calls, so rather
+    /// than re-searching with the same short, fixed budget forever (which
+    /// would never get stronger no matter how long the GUI lets us ponder),
+    /// the per-call budget doubles on every iteration up to
+    /// `PONDER_MAX_BURST_MS`, approximating incremental deepening.
+    fn spawn(game: Chess, mut bot: Bot<Chess>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let best_move = Arc::new(Mutex::new(None));
+
+        let handle = {
+            let stop = Arc::clone(&stop);
+            let best_move = Arc::clone(&best_move);
+            thread::spawn(move || {
+                let mut burst = Duration::from_millis(100);
+                while !stop.load(Ordering::Relaxed) {
+                    if let Some(mov) = bot.select(&game, burst) {
+                        *best_move.lock().unwrap() = Some(mov);
+                    }
+                    burst = (burst * 2).min(Duration::from_millis(PONDER_MAX_BURST_MS));
+                }
+            })
+        };
+
+        BackgroundSearch {
+            handle,
+            stop,
+            best_move,
+        }
+    }
+
+    /// Signals the worker thread to stop and returns the best move it had
+    /// found so far, if any.
+    fn stop_and_join(self) -> Option<Move> {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+        self.best_move.lock().unwrap().take()
+    }
+}
+
+/// Picks a move for a weakened engine: the real `best` move found by search
+/// is still used at full strength, but (unless `elo` is at `MAX_ELO`) the
+/// engine instead samples among all legal moves, weighted by a one-ply
+/// static evaluation, with a temperature that widens as `elo` drops.
+fn weakened_move(game: &Chess, best: Move, elo: i32) -> Move {
+    let (_, actions) = game.actions(&game.0.turn());
+
+    if elo >= MAX_ELO || actions.len() <= 1 {
+        return best;
+    }
+
+    let best_uci = Uci::from_move(&game.0, &best).to_string();
+    let player = game.0.turn();
+    let mut scored: Vec<(Move, i32)> = actions
+        .into_iter()
+        .map(|mov| {
+            let fitness = game.clone().execute(&mov, &player);
+            (mov, fitness)
+        })
+        .collect();
+
+    // nudge ties in favor of the move the full search actually found and
+    // recommends, without lifting it out of the natural score range (that
+    // would make the softmax below always pick it, defeating the point of
+    // sampling at all).
+    if let Some(slot) = scored
+        .iter_mut()
+        .find(|(mov, _)| Uci::from_move(&game.0, mov).to_string() == best_uci)
+    {
+        slot.1 = slot.1.saturating_add(1);
+    }
+
+    let temperature = 1.0 + f64::from(MAX_ELO - elo.clamp(MIN_ELO, MAX_ELO)) / 40.0;
+    sample_by_temperature(scored, temperature)
+}
+
+/// Runs a single `bot.select` over the whole of `total_time` and prints one
+/// `info` line once it returns, so GUIs get at least one line of feedback per
+/// move without the search's time budget being fragmented. `rubot::Bot`
+/// doesn't expose its own depth, node count or principal variation, so these
+/// are approximated: `depth` is reported as `1`, `nodes`/`nps` count the root
+/// moves considered, `score` is a one-ply static evaluation of the move
+/// `select` returned, and `pv` is just that move.
+///
+/// `total_time` of zero (reachable via `go movetime 0` or `go depth 0`) is
+/// bumped up to `MIN_MOVE_TIME_MS`, since giving `bot.select` no time at all
+/// would leave it nothing to search.
+fn search_with_info(game: &Chess, bot: &mut Bot<Chess>, total_time: Duration) -> Move {
+    let root_moves = game.actions(&game.0.turn()).1.len().max(1) as u64;
+    let start = Instant::now();
+
+    let search_time = total_time.max(Duration::from_millis(MIN_MOVE_TIME_MS));
+    let best = bot
+        .select(game, search_time)
+        .expect("it's our turn, so at least one legal move exists");
+
+    print_info(game, 1, &best, start.elapsed(), root_moves);
+
+    best
+}
+
+/// Prints a single UCI `info` line, in the same hand-formatted style as the
+/// `bestmove` response below.
+fn print_info(game: &Chess, depth: u32, mov: &Move, elapsed: Duration, nodes: u64) {
+    let fitness = game.clone().execute(mov, &game.0.turn());
+    let time_ms = elapsed.as_millis().max(1) as u64;
+    let nps = nodes * 1000 / time_ms;
+    let pv = Uci::from_move(&game.0, mov);
+
+    if fitness == std::i32::MAX {
+        println!(
+            "info depth {} score mate 1 time {} nodes {} nps {} pv {}",
+            depth, time_ms, nodes, nps, pv
+        );
+    } else if fitness == std::i32::MIN {
+        println!(
+            "info depth {} score mate -1 time {} nodes {} nps {} pv {}",
+            depth, time_ms, nodes, nps, pv
+        );
+    } else {
+        println!(
+            "info depth {} score cp {} time {} nodes {} nps {} pv {}",
+            depth, fitness, time_ms, nodes, nps, pv
+        );
+    }
+}
+
+/// Softmax-samples a move from `scored`, favoring higher fitness values more
+/// strongly as `temperature` approaches `0`.
+fn sample_by_temperature(scored: Vec<(Move, i32)>, temperature: f64) -> Move {
+    let max = scored
+        .iter()
+        .map(|(_, fitness)| *fitness)
+        .max()
+        .unwrap_or(0);
+    let weights: Vec<f64> = scored
+        .iter()
+        .map(|(_, fitness)| ((f64::from(*fitness) - f64::from(max)) / temperature).exp())
+        .collect();
+    let total: f64 = weights.iter().sum();
+
+    let mut pick = rand::thread_rng().gen::<f64>() * total;
+    for (mov, weight) in scored.iter().zip(&weights) {
+        if pick < *weight {
+            return mov.0.clone();
+        }
+        pick -= weight;
+    }
+
+    scored.last().expect("scored is non-empty").0.clone()
+}
+
 fn main() {
     let mut game = Chess::default();
     let mut bot = Bot::new(Color::Black);
+    let mut options = EngineOptions::default();
+    let mut pondering: Option<BackgroundSearch> = None;
     loop {
         let mut input = String::new();
         io::stdin()
@@ -89,27 +493,81 @@ fn main() {
                         name: Some("rubot".to_owned()),
                         author: Some("lncr/Bastian Kauschke".to_owned()),
                     });
+                    respond(UciMessage::Option(UciOptionConfig::Spin {
+                        name: "Depth".to_owned(),
+                        default: Some(0),
+                        min: Some(0),
+                        max: Some(60),
+                    }));
+                    respond(UciMessage::Option(UciOptionConfig::Spin {
+                        name: "MoveOverhead".to_owned(),
+                        default: Some(30),
+                        min: Some(0),
+                        max: Some(5000),
+                    }));
+                    respond(UciMessage::Option(UciOptionConfig::Check {
+                        name: "UCI_LimitStrength".to_owned(),
+                        default: Some(false),
+                    }));
+                    respond(UciMessage::Option(UciOptionConfig::Spin {
+                        name: "UCI_Elo".to_owned(),
+                        default: Some(MAX_ELO as i64),
+                        min: Some(MIN_ELO as i64),
+                        max: Some(MAX_ELO as i64),
+                    }));
                     respond(UciMessage::UciOk);
                 }
                 UciMessage::IsReady => {
                     respond(UciMessage::ReadyOk);
                 }
+                UciMessage::SetOption { name, value } => match name.as_str() {
+                    "Depth" => {
+                        options.depth = value
+                            .as_deref()
+                            .and_then(|v| v.parse::<u8>().ok())
+                            .filter(|&depth| depth > 0)
+                    }
+                    "MoveOverhead" => {
+                        if let Some(ms) = value.as_deref().and_then(|v| v.parse::<u64>().ok()) {
+                            options.move_overhead = Duration::from_millis(ms);
+                        }
+                    }
+                    "UCI_LimitStrength" => {
+                        if let Some(limit) = value.as_deref().and_then(|v| v.parse::<bool>().ok()) {
+                            options.limit_strength = limit;
+                        }
+                    }
+                    "UCI_Elo" => {
+                        if let Some(elo) = value.as_deref().and_then(|v| v.parse::<i32>().ok()) {
+                            options.elo = elo.clamp(MIN_ELO, MAX_ELO);
+                        }
+                    }
+                    _ => log!("ERROR: can't handle this right now: {}", clone),
+                },
                 UciMessage::UciNewGame => {
-                    game = Chess(shakmaty::Chess::default());
+                    if let Some(search) = pondering.take() {
+                        search.stop_and_join();
+                    }
+                    game = Chess::default();
                 }
                 UciMessage::Position {
                     startpos,
                     fen,
                     moves,
                 } => {
+                    if let Some(search) = pondering.take() {
+                        search.stop_and_join();
+                    }
+
                     if startpos {
-                        game = Chess(shakmaty::Chess::default());
+                        game = Chess::default();
                     } else if let Some(fen) = fen {
                         game = Chess(
                             shakmaty::Chess::from_setup(
                                 &Fen::from_ascii(fen.as_str().as_bytes()).unwrap(),
                             )
                             .unwrap(),
+                            Vec::new(),
                         );
                     }
 
@@ -119,6 +577,10 @@ fn main() {
                             .to_move(&game.0)
                             .unwrap();
                         game.0.play_unchecked(mov);
+                        // record the position actually reached so `execute`'s
+                        // repetition check sees real repeats, not just ones
+                        // within a single speculative search branch.
+                        game.1.push(repetition_key(&game.0));
                     }
 
                     bot = rubot::Bot::new(game.0.turn());
@@ -127,12 +589,21 @@ fn main() {
                     time_control,
                     search_control,
                 } => {
+                    if matches!(
+                        time_control,
+                        Some(UciTimeControl::Ponder) | Some(UciTimeControl::Infinite)
+                    ) {
+                        pondering = Some(BackgroundSearch::spawn(
+                            game.clone(),
+                            Bot::new(game.0.turn()),
+                        ));
+                        continue;
+                    }
+
                     let mut move_time = 5000;
                     if let Some(time_control) = time_control {
                         match time_control {
-                            UciTimeControl::Ponder | UciTimeControl::Infinite => {
-                                log!("ERROR: can't handle this right now: {}", clone)
-                            }
+                            UciTimeControl::Ponder | UciTimeControl::Infinite => unreachable!(),
                             UciTimeControl::TimeLeft {
                                 white_time,
                                 black_time,
@@ -140,18 +611,19 @@ fn main() {
                                 black_increment,
                                 moves_to_go,
                             } => {
-                                if moves_to_go.is_some() {
-                                    log!("ERROR: can't handle this right now: {}", clone)
-                                }
-
-                                if game.0.turn() == Color::Black {
-                                    if let (Some(bt), Some(bi)) = (black_time, black_increment) {
-                                        move_time = std::cmp::min(bi / 2 + bt / 20, 7000 + bi);
-                                    }
+                                let (time, increment) = if game.0.turn() == Color::Black {
+                                    (black_time, black_increment)
                                 } else {
-                                    if let (Some(wt), Some(wi)) = (white_time, white_increment) {
-                                        move_time = std::cmp::min(wi / 2 + wt / 20, 7000 + wi);
-                                    }
+                                    (white_time, white_increment)
+                                };
+
+                                if let Some(time) = time {
+                                    move_time = allocate_move_time(
+                                        time,
+                                        increment.unwrap_or(0),
+                                        moves_to_go,
+                                        options.move_overhead,
+                                    );
                                 }
                             }
                             UciTimeControl::MoveTime(time) => move_time = time,
@@ -159,22 +631,42 @@ fn main() {
                     }
 
                     if let Some(search_control) = search_control {
-                        if !search_control.search_moves.is_empty()
-                            || search_control.mate.is_some()
-                            || search_control.depth.is_some()
-                            || search_control.nodes.is_some()
+                        if !search_control.search_moves.is_empty() || search_control.mate.is_some()
                         {
                             log!("ERROR: can't handle this right now: {}", clone)
                         }
+
+                        if let Some(depth) = search_control.depth {
+                            move_time = duration_for_depth(depth).as_millis() as u64;
+                        } else if search_control.nodes.is_some() {
+                            // node counts can't be translated directly, fall back to a
+                            // generous duration cap so the search still runs instead of
+                            // being rejected.
+                            move_time = duration_for_depth(60).as_millis() as u64;
+                        } else if let Some(depth) = options.depth {
+                            move_time = duration_for_depth(depth).as_millis() as u64;
+                        }
+                    } else if let Some(depth) = options.depth {
+                        move_time = duration_for_depth(depth).as_millis() as u64;
                     }
 
-                    println!(
-                        "bestmove {}",
-                        Uci::from_move(
-                            &game.0,
-                            &bot.select(&game, Duration::from_millis(move_time)).unwrap()
-                        )
-                    );
+                    let best = search_with_info(&game, &mut bot, Duration::from_millis(move_time));
+                    let mov = if options.limit_strength {
+                        weakened_move(&game, best, options.elo)
+                    } else {
+                        best
+                    };
+                    println!("bestmove {}", Uci::from_move(&game.0, &mov));
+                }
+                UciMessage::Stop | UciMessage::PonderHit => {
+                    if let Some(search) = pondering.take() {
+                        let mov = search
+                            .stop_and_join()
+                            .or_else(|| bot.select(&game, Duration::from_millis(100)));
+                        if let Some(mov) = mov {
+                            println!("bestmove {}", Uci::from_move(&game.0, &mov));
+                        }
+                    }
                 }
                 UciMessage::Quit => {
                     std::process::exit(0);
@@ -184,3 +676,55 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate_move_time_never_exceeds_time_left() {
+        // a completely drained clock must never get more time than it has,
+        // not even the `MIN_MOVE_TIME_MS` floor.
+        assert_eq!(allocate_move_time(0, 0, None, Duration::from_millis(30)), 0);
+    }
+
+    #[test]
+    fn allocate_move_time_clock_under_move_overhead() {
+        // `time_left` smaller than `move_overhead` drives `available` to 0;
+        // the result must still be clamped to what's actually left (10ms),
+        // not the `MIN_MOVE_TIME_MS` floor (50ms).
+        assert_eq!(
+            allocate_move_time(10, 0, None, Duration::from_millis(30)),
+            10
+        );
+    }
+
+    #[test]
+    fn allocate_move_time_floors_to_minimum_when_clock_allows() {
+        // plenty of time left, but a tiny per-move share: the floor kicks in
+        // without being capped down by `time_left`.
+        assert_eq!(
+            allocate_move_time(1_000, 0, Some(200), Duration::from_millis(30)),
+            MIN_MOVE_TIME_MS
+        );
+    }
+
+    #[test]
+    fn allocate_move_time_honors_moves_to_go() {
+        let with_moves_to_go = allocate_move_time(60_000, 0, Some(10), Duration::from_millis(30));
+        let without_moves_to_go = allocate_move_time(60_000, 0, None, Duration::from_millis(30));
+        // 10 moves to go budgets a much larger share per move than the
+        // "assume ~30 moves remain" fallback used when it's absent.
+        assert!(with_moves_to_go > without_moves_to_go);
+    }
+
+    #[test]
+    fn allocate_move_time_adds_increment_but_stays_bounded() {
+        let available = 10_000 - 30;
+        let budget = allocate_move_time(10_000, 5_000, None, Duration::from_millis(30));
+        // the increment bonus is real, but can't push the result past
+        // what's actually available this move.
+        assert!(budget <= available);
+        assert!(budget > available / 30);
+    }
+}